@@ -1,3 +1,5 @@
+use std::collections::VecDeque;
+use std::future::Future;
 use std::io::Write;
 use std::pin::Pin;
 use std::task::{Context, Poll};
@@ -6,8 +8,12 @@ use std::{io, time};
 use actix_codec::{AsyncRead, AsyncWrite, Framed, ReadBuf};
 use bytes::buf::BufMut;
 use bytes::{Bytes, BytesMut};
+use futures_channel::{mpsc, oneshot};
 use futures_core::Stream;
-use futures_util::{future::poll_fn, SinkExt as _};
+use futures_util::{
+    future::{poll_fn, select, Either},
+    SinkExt as _, StreamExt as _,
+};
 
 use crate::error::PayloadError;
 use crate::h1;
@@ -24,18 +30,9 @@ use super::error::{ConnectError, SendRequestError};
 use super::pool::Acquired;
 use crate::body::{BodySize, MessageBody};
 
-pub(crate) async fn send_request<T, B>(
-    io: T,
-    mut head: RequestHeadType,
-    body: B,
-    created: time::Instant,
-    acquired: Acquired<T>,
-) -> Result<(ResponseHead, Payload), SendRequestError>
-where
-    T: AsyncRead + AsyncWrite + Unpin + 'static,
-    B: MessageBody,
-{
-    // set request host header
+/// Fill in the request's `Host` header from its URI authority, if not
+/// already set.
+fn fixup_host_header(head: &mut RequestHeadType) -> Result<(), SendRequestError> {
     if !head.as_ref().headers.contains_key(HOST)
         && !head.extra_headers().iter().any(|h| h.contains_key(HOST))
     {
@@ -62,6 +59,26 @@ where
         }
     }
 
+    Ok(())
+}
+
+pub use crate::h1::ParserConfig;
+
+pub(crate) async fn send_request<T, B>(
+    io: T,
+    mut head: RequestHeadType,
+    body: B,
+    created: time::Instant,
+    acquired: Acquired<T>,
+    expect_continue_timeout: Option<time::Duration>,
+    parser_config: ParserConfig,
+) -> Result<(ResponseHead, Payload), SendRequestError>
+where
+    T: AsyncRead + AsyncWrite + Unpin + 'static,
+    B: MessageBody,
+{
+    fixup_host_header(&mut head)?;
+
     let io = H1Connection {
         created,
         acquired,
@@ -69,7 +86,7 @@ where
     };
 
     // create Framed and prepare sending request
-    let mut framed = Framed::new(io, h1::ClientCodec::default());
+    let mut framed = Framed::new(io, h1::ClientCodec::with_config(parser_config));
 
     // Check EXPECT header and enable expect handle flag accordingly.
     //
@@ -96,13 +113,37 @@ where
 
     // special handle for EXPECT request.
     let (do_send, mut res_head) = if is_expect {
-        let head = poll_fn(|cx| pin_framed.as_mut().poll_next(cx))
-            .await
-            .ok_or(ConnectError::Disconnected)??;
+        let wait_continue = poll_fn(|cx| pin_framed.as_mut().poll_next(cx));
+
+        let head = match expect_continue_timeout {
+            // no timeout configured, wait for the interim response indefinitely,
+            // same as before this option existed.
+            None => Some(wait_continue.await.ok_or(ConnectError::Disconnected)??),
+
+            Some(timeout) => {
+                let timer = actix_rt::time::sleep(timeout);
+                actix_rt::pin!(timer);
+
+                match select(wait_continue, timer).await {
+                    Either::Left((head, _)) => {
+                        Some(head.ok_or(ConnectError::Disconnected)??)
+                    }
+                    // RFC 7231 §5.1.1: a client that does not receive a final or
+                    // interim response within a reasonable period of time SHOULD
+                    // send the request body as if a `100 Continue` had been
+                    // received, rather than waiting forever for a server that
+                    // doesn't understand (or ignores) `Expect: 100-continue`.
+                    Either::Right((_, _)) => None,
+                }
+            }
+        };
 
-        // return response head in case status code is not continue
-        // and current head would be used as final response head.
-        (head.status == StatusCode::CONTINUE, Some(head))
+        match head {
+            // return response head in case status code is not continue
+            // and current head would be used as final response head.
+            Some(head) => (head.status == StatusCode::CONTINUE, Some(head)),
+            None => (true, None),
+        }
     } else {
         (true, None)
     };
@@ -141,12 +182,13 @@ where
 pub(crate) async fn open_tunnel<T>(
     io: T,
     head: RequestHeadType,
+    parser_config: ParserConfig,
 ) -> Result<(ResponseHead, Framed<T, h1::ClientCodec>), SendRequestError>
 where
     T: AsyncRead + AsyncWrite + Unpin + 'static,
 {
     // create Framed and send request
-    let mut framed = Framed::new(io, h1::ClientCodec::default());
+    let mut framed = Framed::new(io, h1::ClientCodec::with_config(parser_config));
     framed.send((head, BodySize::None).into()).await?;
 
     // read response
@@ -157,23 +199,645 @@ where
     Ok((head, framed))
 }
 
+type ConnectionItem<B> = (
+    RequestHeadType,
+    B,
+    oneshot::Sender<Result<(ResponseHead, Payload), SendRequestError>>,
+);
+
+/// Performs the HTTP/1 handshake over a caller-supplied I/O object.
+///
+/// Unlike [`send_request`], the returned connection is not taken from, nor
+/// ever released back to, the connection pool: the caller owns `io` for as
+/// long as it is used. This is useful for running actix-http's client codec
+/// over a connection set up out of band, e.g. a pre-negotiated TLS session
+/// or a Unix domain socket.
+///
+/// Returns a cheaply-clonable [`SendRequest`] handle for submitting requests
+/// and a [`Connection`] future that must be polled (typically spawned via
+/// `actix_rt::spawn`) to drive I/O; requests submitted through the handle
+/// make no progress until the connection future is polled.
+///
+/// Requests here are fully serialized: one request's response must be read
+/// before the next request is written. See [`handshake_pipelined`] for a
+/// variant that writes several requests back-to-back.
+pub async fn handshake<T, B>(
+    io: T,
+    parser_config: ParserConfig,
+) -> Result<(SendRequest<B>, Connection<T, B>), SendRequestError>
+where
+    T: AsyncRead + AsyncWrite + Unpin + 'static,
+    B: MessageBody + Unpin + 'static,
+{
+    let (tx, rx) = mpsc::unbounded();
+
+    Ok((
+        SendRequest { tx },
+        Connection {
+            inner: Box::pin(drive_connection(io, rx, parser_config)),
+            _t: std::marker::PhantomData,
+        },
+    ))
+}
+
+/// A cheaply-clonable handle for submitting requests over a connection
+/// established by [`handshake`].
+pub struct SendRequest<B> {
+    tx: mpsc::UnboundedSender<ConnectionItem<B>>,
+}
+
+impl<B> Clone for SendRequest<B> {
+    fn clone(&self) -> Self {
+        SendRequest {
+            tx: self.tx.clone(),
+        }
+    }
+}
+
+impl<B> SendRequest<B>
+where
+    B: MessageBody + Unpin + 'static,
+{
+    /// Send a request over the paired connection, resolving to the response
+    /// head and payload once they have been read off the wire.
+    ///
+    /// The future only makes progress while the [`Connection`] returned
+    /// alongside this handle is being polled.
+    pub async fn send_request(
+        &self,
+        head: RequestHeadType,
+        body: B,
+    ) -> Result<(ResponseHead, Payload), SendRequestError> {
+        let (tx, rx) = oneshot::channel();
+
+        self.tx
+            .unbounded_send((head, body, tx))
+            .map_err(|_| SendRequestError::Connect(ConnectError::Disconnected))?;
+
+        rx.await
+            .map_err(|_| SendRequestError::Connect(ConnectError::Disconnected))?
+    }
+}
+
+/// Drives I/O for a connection established by [`handshake`].
+///
+/// Must be polled to completion (typically spawned via `actix_rt::spawn`)
+/// for requests submitted through the paired [`SendRequest`] handle to make
+/// progress. Resolves once the paired handle (and all of its clones) have
+/// been dropped and any in-flight request has completed, or once the
+/// connection is closed by the peer.
+pub struct Connection<T, B> {
+    inner: Pin<Box<dyn Future<Output = Result<(), SendRequestError>>>>,
+    _t: std::marker::PhantomData<(T, B)>,
+}
+
+impl<T, B> Future for Connection<T, B> {
+    type Output = Result<(), SendRequestError>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        self.inner.as_mut().poll(cx)
+    }
+}
+
+/// I/O wrapper for a [`handshake`]-driven connection.
+///
+/// Hands `io` back to [`drive_connection`]'s loop (via `ret`) once the
+/// response body guarded by this wrapper has been fully read and the
+/// connection is eligible to carry the next queued request; on error, or
+/// when the response indicated the connection should be closed, `io` is
+/// dropped instead.
+struct OwnedIo<T> {
+    io: Option<T>,
+    ret: Option<oneshot::Sender<Option<T>>>,
+}
+
+impl<T> Drop for OwnedIo<T> {
+    fn drop(&mut self) {
+        // Reaching here with `ret` still set means `on_release` never ran,
+        // i.e. the response payload was dropped before its body was fully
+        // read. The socket's read cursor is left mid-body, so it can't be
+        // handed back as a fresh connection for the next queued request —
+        // close it instead, mirroring `PipelinePlStream`'s early-drop
+        // handling.
+        if let Some(ret) = self.ret.take() {
+            let _ = ret.send(None);
+        }
+    }
+}
+
+impl<T: AsyncRead + AsyncWrite + Unpin + 'static> ReleasableIo for OwnedIo<T> {
+    fn on_release(&mut self, keep_alive: bool) {
+        let io = if keep_alive { self.io.take() } else { None };
+        if let Some(ret) = self.ret.take() {
+            let _ = ret.send(io);
+        }
+    }
+}
+
+impl<T: AsyncRead + AsyncWrite + Unpin + 'static> AsyncRead for OwnedIo<T> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        Pin::new(self.io.as_mut().unwrap()).poll_read(cx, buf)
+    }
+}
+
+impl<T: AsyncRead + AsyncWrite + Unpin + 'static> AsyncWrite for OwnedIo<T> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(self.io.as_mut().unwrap()).poll_write(cx, buf)
+    }
+
+    fn poll_flush(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<io::Result<()>> {
+        Pin::new(self.io.as_mut().unwrap()).poll_flush(cx)
+    }
+
+    fn poll_shutdown(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<(), io::Error>> {
+        Pin::new(self.io.as_mut().unwrap()).poll_shutdown(cx)
+    }
+}
+
+/// Drains the request queue one item at a time, handing `io` back to
+/// itself (via the item's reclaim channel) once each response body has
+/// been fully read, so the next queued request can reuse the connection.
+async fn drive_connection<T, B>(
+    mut io: T,
+    mut rx: mpsc::UnboundedReceiver<ConnectionItem<B>>,
+    parser_config: ParserConfig,
+) -> Result<(), SendRequestError>
+where
+    T: AsyncRead + AsyncWrite + Unpin + 'static,
+    B: MessageBody + Unpin + 'static,
+{
+    while let Some((head, body, tx)) = rx.next().await {
+        let (result, reclaim) = send_one(io, head, body, parser_config).await;
+        let failed = result.is_err();
+        let _ = tx.send(result);
+
+        if failed {
+            break;
+        }
+
+        match reclaim.await {
+            Ok(Some(next_io)) => io = next_io,
+            _ => break,
+        }
+    }
+
+    Ok(())
+}
+
+/// Same request/response lifecycle as [`send_request`], but driving a plain
+/// [`OwnedIo`]-wrapped `io` instead of a pool-managed [`H1Connection`], and
+/// without its `Expect: 100-continue` handling: the body is always written
+/// right after the head.
+///
+/// Returns the result of the exchange together with a receiver that
+/// resolves to `io` once it is free to carry another request (or to `None`
+/// if the connection must be closed).
+async fn send_one<T, B>(
+    io: T,
+    mut head: RequestHeadType,
+    body: B,
+    parser_config: ParserConfig,
+) -> (
+    Result<(ResponseHead, Payload), SendRequestError>,
+    oneshot::Receiver<Option<T>>,
+)
+where
+    T: AsyncRead + AsyncWrite + Unpin + 'static,
+    B: MessageBody,
+{
+    let (ret_tx, ret_rx) = oneshot::channel();
+
+    let result = async {
+        fixup_host_header(&mut head)?;
+
+        let io = OwnedIo {
+            io: Some(io),
+            ret: Some(ret_tx),
+        };
+
+        let mut framed = Framed::new(io, h1::ClientCodec::with_config(parser_config));
+        framed.send((head, body.size()).into()).await?;
+
+        let mut pin_framed = Pin::new(&mut framed);
+
+        // Body must go out before we wait for the response head: the peer
+        // may not start responding until it has read the request body, so
+        // polling for the response first can deadlock the connection on any
+        // request with a non-empty body. `write_pipelined_request` uses the
+        // same order, as does `send_request` for requests without `Expect`.
+        match body.size() {
+            BodySize::None | BodySize::Empty | BodySize::Sized(0) => {}
+            _ => send_body(body, pin_framed.as_mut()).await?,
+        };
+
+        let head = poll_fn(|cx| pin_framed.as_mut().poll_next(cx))
+            .await
+            .ok_or(ConnectError::Disconnected)??;
+
+        match pin_framed.codec_ref().message_type() {
+            h1::MessageType::None => {
+                let keep_alive = pin_framed.codec_ref().keepalive();
+                pin_framed.io_mut().on_release(keep_alive);
+
+                Ok((head, Payload::None))
+            }
+            _ => {
+                let pl: PayloadStream = Box::pin(PlStream::new(framed));
+                Ok((head, pl.into()))
+            }
+        }
+    }
+    .await;
+
+    (result, ret_rx)
+}
+
+/// Performs the HTTP/1 handshake over a caller-supplied I/O object, enabling
+/// request pipelining.
+///
+/// Identical to [`handshake`], except that requests submitted through the
+/// returned [`SendRequest`] handle are written back-to-back onto the wire
+/// without waiting for earlier responses, as permitted for idempotent
+/// requests by RFC 7230 §6.3.2. Responses (and their bodies, which must be
+/// read off the wire before the next response can be parsed) are matched to
+/// requests in FIFO order.
+///
+/// Pipelining stops as soon as a response can't safely be followed by
+/// another on the same connection — a `Connection: close`, or framing that
+/// can't be delimited — at which point the connection finishes the
+/// already-written batch and then closes.
+pub async fn handshake_pipelined<T, B>(
+    io: T,
+    parser_config: ParserConfig,
+) -> Result<(SendRequest<B>, Connection<T, B>), SendRequestError>
+where
+    T: AsyncRead + AsyncWrite + Unpin + 'static,
+    B: MessageBody + Unpin + 'static,
+{
+    let (tx, rx) = mpsc::unbounded();
+
+    Ok((
+        SendRequest { tx },
+        Connection {
+            inner: Box::pin(drive_pipelined_connection(io, rx, parser_config)),
+            _t: std::marker::PhantomData,
+        },
+    ))
+}
+
+/// Payload stream for a response read off a [`handshake_pipelined`]
+/// connection.
+///
+/// Reclaims the underlying `Framed<T, ClientCodec>` (via `ret`) once the
+/// body has been fully read, so [`drive_pipelined_connection`] can resume
+/// reading the response queued behind it off the read half of the split
+/// connection. Responses must be read off the wire strictly in order, so
+/// the *read* side can't move on until this one's body is drained (or the
+/// stream is dropped without being drained, in which case the read side is
+/// closed rather than left in an unknown framing state) — writing newly
+/// queued requests on the write half is unaffected and continues
+/// regardless.
+struct PipelinePlStream<T>
+where
+    T: AsyncRead + Unpin + 'static,
+{
+    framed: Option<Framed<T, h1::ClientPayloadCodec>>,
+    ret: Option<oneshot::Sender<Option<Framed<T, h1::ClientCodec>>>>,
+    parser_config: ParserConfig,
+}
+
+impl<T> Drop for PipelinePlStream<T>
+where
+    T: AsyncRead + Unpin + 'static,
+{
+    fn drop(&mut self) {
+        if let Some(ret) = self.ret.take() {
+            let _ = ret.send(None);
+        }
+    }
+}
+
+impl<T> Stream for PipelinePlStream<T>
+where
+    T: AsyncRead + Unpin + 'static,
+{
+    type Item = Result<Bytes, PayloadError>;
+
+    fn poll_next(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Self::Item>> {
+        let this = self.as_mut().get_mut();
+        let mut framed =
+            Pin::new(this.framed.as_mut().expect("PipelinePlStream polled after completion"));
+
+        match framed.as_mut().next_item(cx)? {
+            Poll::Pending => Poll::Pending,
+            Poll::Ready(Some(chunk)) => {
+                if let Some(chunk) = chunk {
+                    Poll::Ready(Some(Ok(chunk)))
+                } else {
+                    let keep_alive = framed.codec_ref().keepalive();
+                    let framed = this.framed.take().unwrap();
+                    let parser_config = this.parser_config;
+
+                    let next = keep_alive.then(|| {
+                        framed.into_map_codec(|_| h1::ClientCodec::with_config(parser_config))
+                    });
+
+                    if let Some(ret) = this.ret.take() {
+                        let _ = ret.send(next);
+                    }
+
+                    Poll::Ready(None)
+                }
+            }
+            Poll::Ready(None) => {
+                if let Some(ret) = this.ret.take() {
+                    let _ = ret.send(None);
+                }
+
+                Poll::Ready(None)
+            }
+        }
+    }
+}
+
+/// Writes one request's head and body onto `framed`, without waiting for its
+/// response.
+async fn write_pipelined_request<T, B>(
+    framed: &mut Framed<T, h1::ClientCodec>,
+    mut head: RequestHeadType,
+    body: B,
+) -> Result<(), SendRequestError>
+where
+    T: AsyncWrite + Unpin + 'static,
+    B: MessageBody,
+{
+    fixup_host_header(&mut head)?;
+
+    framed.send((head, body.size()).into()).await?;
+
+    match body.size() {
+        BodySize::None | BodySize::Empty | BodySize::Sized(0) => {}
+        _ => send_body(body, Pin::new(framed)).await?,
+    }
+
+    Ok(())
+}
+
+/// Fails every request still waiting on a response, plus any that were
+/// queued but never written, with [`ConnectError::Disconnected`].
+fn fail_pipelined_remaining<B>(
+    rx: &mut mpsc::UnboundedReceiver<ConnectionItem<B>>,
+    pending: &mut VecDeque<oneshot::Sender<Result<(ResponseHead, Payload), SendRequestError>>>,
+) {
+    while let Some(tx) = pending.pop_front() {
+        let _ = tx.send(Err(SendRequestError::Connect(ConnectError::Disconnected)));
+    }
+
+    while let Ok(Some((_, _, tx))) = rx.try_next() {
+        let _ = tx.send(Err(SendRequestError::Connect(ConnectError::Disconnected)));
+    }
+}
+
+/// The read half's state between responses: either idle waiting for the
+/// next response head, or a prior response's body has been handed to the
+/// caller and we're waiting for it to be reclaimed (drained or dropped)
+/// before the next head can be decoded.
+enum ReadState<T>
+where
+    T: AsyncRead + Unpin + 'static,
+{
+    Head(Framed<T, h1::ClientCodec>),
+    Body(oneshot::Receiver<Option<Framed<T, h1::ClientCodec>>>),
+}
+
+/// Drives a pipelined connection: writes queued requests back-to-back and
+/// matches decoded responses to them in FIFO order.
+///
+/// The connection is split into independent read and write halves so that
+/// writing newly queued requests never waits on a prior response's body
+/// being drained by the caller — only the *read* side is serialized behind
+/// that drain, per RFC 7230 §6.3.2's FIFO ordering requirement.
+async fn drive_pipelined_connection<T, B>(
+    io: T,
+    mut rx: mpsc::UnboundedReceiver<ConnectionItem<B>>,
+    parser_config: ParserConfig,
+) -> Result<(), SendRequestError>
+where
+    T: AsyncRead + AsyncWrite + Unpin + 'static,
+    B: MessageBody + Unpin + 'static,
+{
+    let (read_half, write_half) = tokio::io::split(io);
+    let mut write_framed = Framed::new(write_half, h1::ClientCodec::with_config(parser_config));
+    let mut read_state = ReadState::Head(Framed::new(
+        read_half,
+        h1::ClientCodec::with_config(parser_config),
+    ));
+
+    let mut pending: VecDeque<
+        oneshot::Sender<Result<(ResponseHead, Payload), SendRequestError>>,
+    > = VecDeque::new();
+    let mut closing = false;
+
+    loop {
+        // write every request already queued, back-to-back, as long as
+        // nothing has told us to stop pipelining yet — independent of
+        // whatever state the read side is in
+        while !closing {
+            match rx.try_next() {
+                Ok(Some((head, body, tx))) => {
+                    match write_pipelined_request(&mut write_framed, head, body).await {
+                        Ok(()) => pending.push_back(tx),
+                        Err(e) => {
+                            let _ = tx.send(Err(e));
+                            closing = true;
+                        }
+                    }
+                }
+                Ok(None) | Err(_) => break,
+            }
+        }
+
+        if pending.is_empty() && matches!(read_state, ReadState::Head(_)) {
+            if closing {
+                return Ok(());
+            }
+
+            // nothing in flight and no body still draining: block for the
+            // next request instead of polling the read half for a response
+            // that can't possibly arrive
+            match rx.next().await {
+                Some((head, body, tx)) => {
+                    match write_pipelined_request(&mut write_framed, head, body).await {
+                        Ok(()) => pending.push_back(tx),
+                        Err(e) => {
+                            let _ = tx.send(Err(e));
+                            closing = true;
+                        }
+                    }
+                    continue;
+                }
+                None => return Ok(()),
+            }
+        }
+
+        match read_state {
+            ReadState::Body(mut ret_rx) => {
+                // a prior response's body is still being drained by the
+                // caller; keep writing anything newly queued while we wait
+                // for it, instead of blocking the whole connection on it
+                match select(rx.next(), &mut ret_rx).await {
+                    Either::Left((Some((head, body, tx)), _)) => {
+                        if closing {
+                            // pipelining has stopped: don't write anything
+                            // behind the batch already on the wire
+                            let _ = tx.send(Err(SendRequestError::Connect(
+                                ConnectError::Disconnected,
+                            )));
+                        } else {
+                            match write_pipelined_request(&mut write_framed, head, body).await {
+                                Ok(()) => pending.push_back(tx),
+                                Err(e) => {
+                                    let _ = tx.send(Err(e));
+                                    closing = true;
+                                }
+                            }
+                        }
+                        read_state = ReadState::Body(ret_rx);
+                    }
+                    Either::Left((None, _)) => {
+                        // every `SendRequest` handle is gone: nothing left
+                        // to write, so just wait out the in-flight body's
+                        // reclaim directly instead of re-polling an
+                        // already-exhausted `rx` in a tight loop
+                        closing = true;
+                        match (&mut ret_rx).await {
+                            Ok(Some(next_framed)) => read_state = ReadState::Head(next_framed),
+                            _ => {
+                                fail_pipelined_remaining(&mut rx, &mut pending);
+                                return Ok(());
+                            }
+                        }
+                    }
+                    Either::Right((Ok(Some(next_framed)), _)) => {
+                        read_state = ReadState::Head(next_framed);
+                    }
+                    Either::Right((Ok(None), _)) | Either::Right((Err(_), _)) => {
+                        fail_pipelined_remaining(&mut rx, &mut pending);
+                        return Ok(());
+                    }
+                }
+            }
+            ReadState::Head(mut framed) => {
+                // `pending` is non-empty here, guarded above
+                let head = match poll_fn(|cx| Pin::new(&mut framed).poll_next(cx)).await {
+                    Some(Ok(head)) => head,
+                    Some(Err(e)) => {
+                        if let Some(tx) = pending.pop_front() {
+                            let _ = tx.send(Err(e));
+                        }
+                        fail_pipelined_remaining(&mut rx, &mut pending);
+                        return Ok(());
+                    }
+                    None => {
+                        fail_pipelined_remaining(&mut rx, &mut pending);
+                        return Ok(());
+                    }
+                };
+
+                let tx = pending
+                    .pop_front()
+                    .expect("pending checked non-empty above");
+
+                // RFC 7230 §6.3.2: a response that can't safely be followed
+                // by another on this connection ends the pipelined batch —
+                // either it said so explicitly (`Connection: close`, or
+                // HTTP/1.0 without `keep-alive`), or its body has no
+                // delimiter of its own and can only be read by reading
+                // until the connection closes, which would make the next
+                // pipelined response's bytes indistinguishable from more of
+                // this one's body.
+                if !framed.codec_ref().keepalive() || framed.codec_ref().has_indeterminate_framing()
+                {
+                    closing = true;
+                }
+
+                match framed.codec_ref().message_type() {
+                    h1::MessageType::None => {
+                        let _ = tx.send(Ok((head, Payload::None)));
+                        read_state = ReadState::Head(framed);
+                    }
+                    _ => {
+                        let (ret_tx, ret_rx) = oneshot::channel();
+                        let payload_framed =
+                            framed.into_map_codec(|codec| codec.into_payload_codec());
+
+                        let pl: PayloadStream = Box::pin(PipelinePlStream {
+                            framed: Some(payload_framed),
+                            ret: Some(ret_tx),
+                            parser_config,
+                        });
+                        let _ = tx.send(Ok((head, pl.into())));
+
+                        read_state = ReadState::Body(ret_rx);
+                    }
+                }
+            }
+        }
+    }
+}
+
 /// send request body to the peer
+///
+/// The amount buffered between flushes adapts to recent write pressure,
+/// mirroring the strategy hyper's buffered I/O uses: it doubles toward the
+/// codec's configured max (see [`h1::ClientCodec::with_write_buffer_bounds`])
+/// when a flush drains a buffer that filled up completely, and halves back
+/// toward the configured min when a flush drains one that stayed mostly
+/// empty.
+///
+/// The target is compared against bytes *we've* buffered this round, not
+/// `Framed`'s own write-buffer occupancy: the latter is sized for ordinary
+/// request/response traffic and is typically far smaller than the grown
+/// target, so gating on it would make the adaptive growth above inert.
 pub(crate) async fn send_body<T, B>(
     body: B,
     mut framed: Pin<&mut Framed<T, h1::ClientCodec>>,
 ) -> Result<(), SendRequestError>
 where
-    T: AsyncRead + AsyncWrite + Unpin + 'static,
+    T: AsyncWrite + Unpin + 'static,
     B: MessageBody,
 {
     actix_rt::pin!(body);
 
+    let (min_buffer_size, max_buffer_size) = framed.as_ref().codec_ref().write_buffer_bounds();
+    let mut target_buffer_size = min_buffer_size;
+    let mut buffered = 0;
+
     let mut eof = false;
     while !eof {
-        while !eof && !framed.as_ref().is_write_buf_full() {
+        while !eof && buffered < target_buffer_size {
             match poll_fn(|cx| body.as_mut().poll_next(cx)).await {
                 Some(result) => {
-                    framed.as_mut().write(h1::Message::Chunk(Some(result?)))?;
+                    let chunk = result?;
+                    buffered += chunk.len();
+                    framed.as_mut().write(h1::Message::Chunk(Some(chunk)))?;
                 }
                 None => {
                     eof = true;
@@ -182,6 +846,8 @@ where
             }
         }
 
+        let was_full = !eof && buffered >= target_buffer_size;
+
         if !framed.as_ref().is_write_buf_empty() {
             poll_fn(|cx| match framed.as_mut().flush(cx) {
                 Poll::Ready(Ok(_)) => Poll::Ready(Ok(())),
@@ -196,12 +862,37 @@ where
             })
             .await?;
         }
+
+        target_buffer_size =
+            next_write_buffer_target(target_buffer_size, buffered, was_full, min_buffer_size, max_buffer_size);
+
+        buffered = 0;
     }
 
     framed.get_mut().flush().await?;
     Ok(())
 }
 
+/// Computes the next round's write-buffer target for [`send_body`]: doubles
+/// toward `max` when the buffer filled completely between flushes, halves
+/// back toward `min` once usage drops below a quarter of the current
+/// target, and otherwise holds steady.
+fn next_write_buffer_target(
+    current: usize,
+    buffered: usize,
+    was_full: bool,
+    min: usize,
+    max: usize,
+) -> usize {
+    if was_full {
+        (current * 2).min(max)
+    } else if buffered < current / 4 {
+        (current / 2).max(min)
+    } else {
+        current
+    }
+}
+
 #[doc(hidden)]
 /// HTTP client connection
 pub struct H1Connection<T>
@@ -275,20 +966,37 @@ impl<T: AsyncRead + AsyncWrite + Unpin + 'static> AsyncWrite for H1Connection<T>
     }
 }
 
+/// I/O wrapper that can be told whether the connection it guards should be
+/// kept alive (released back to its owner) or closed, once its response
+/// body has been fully read.
+///
+/// Implemented by [`H1Connection`] (pool-backed) and [`OwnedIo`]
+/// (caller-owned, see [`handshake`]) so [`PlStream`] can drive either kind
+/// of connection without caring which one it holds.
+pub(crate) trait ReleasableIo: AsyncRead + AsyncWrite + Unpin + 'static {
+    fn on_release(&mut self, keep_alive: bool);
+}
+
+impl<T: AsyncRead + AsyncWrite + Unpin + 'static> ReleasableIo for H1Connection<T> {
+    fn on_release(&mut self, keep_alive: bool) {
+        H1Connection::on_release(self, keep_alive)
+    }
+}
+
 #[pin_project::pin_project]
-pub(crate) struct PlStream<Io>
+pub(crate) struct PlStream<C>
 where
-    Io: AsyncRead + AsyncWrite + Unpin + 'static,
+    C: ReleasableIo,
 {
     #[pin]
-    framed: Option<Framed<H1Connection<Io>, h1::ClientPayloadCodec>>,
+    framed: Option<Framed<C, h1::ClientPayloadCodec>>,
 }
 
-impl<Io> PlStream<Io>
+impl<C> PlStream<C>
 where
-    Io: AsyncRead + AsyncWrite + Unpin + 'static,
+    C: ReleasableIo,
 {
-    fn new(framed: Framed<H1Connection<Io>, h1::ClientCodec>) -> Self {
+    fn new(framed: Framed<C, h1::ClientCodec>) -> Self {
         let framed = framed.into_map_codec(|codec| codec.into_payload_codec());
 
         PlStream {
@@ -297,9 +1005,9 @@ where
     }
 }
 
-impl<Io> Stream for PlStream<Io>
+impl<C> Stream for PlStream<C>
 where
-    Io: AsyncRead + AsyncWrite + Unpin + 'static,
+    C: ReleasableIo,
 {
     type Item = Result<Bytes, PayloadError>;
 
@@ -324,3 +1032,104 @@ where
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use actix_codec::Decoder as _;
+    use bytes::BytesMut;
+
+    use super::*;
+
+    /// A response with neither `Content-Length` nor `Transfer-Encoding`
+    /// reports `keepalive() == true` (HTTP/1.1, no `Connection: close`), but
+    /// its body has no delimiter of its own, so pipelining must still stop
+    /// behind it — this is what `has_indeterminate_framing` is for.
+    #[test]
+    fn indeterminate_framing_stops_pipelining_even_when_keepalive() {
+        let mut codec = h1::ClientCodec::with_config(ParserConfig::default());
+        let mut buf = BytesMut::from(&b"HTTP/1.1 200 OK\r\n\r\n"[..]);
+
+        let head = codec.decode(&mut buf).unwrap().expect("head decoded");
+        assert_eq!(head.status, StatusCode::OK);
+
+        assert!(codec.keepalive());
+        assert!(codec.has_indeterminate_framing());
+        assert!(!codec.keepalive() || codec.has_indeterminate_framing());
+    }
+
+    /// A normal `Content-Length`-delimited keep-alive response has no such
+    /// problem: pipelining should keep going behind it.
+    #[test]
+    fn content_length_framing_allows_pipelining() {
+        let mut codec = h1::ClientCodec::with_config(ParserConfig::default());
+        let mut buf =
+            BytesMut::from(&b"HTTP/1.1 200 OK\r\ncontent-length: 5\r\n\r\n"[..]);
+
+        codec.decode(&mut buf).unwrap().expect("head decoded");
+
+        assert!(codec.keepalive());
+        assert!(!codec.has_indeterminate_framing());
+    }
+
+    /// `fail_pipelined_remaining` must fail every request still waiting on a
+    /// response, in FIFO order, plus anything still queued but never
+    /// written — the path taken when the connection closes mid-batch.
+    #[test]
+    fn fail_pipelined_remaining_drains_pending_and_queue() {
+        let (tx, mut rx) = mpsc::unbounded::<ConnectionItem<crate::body::Body>>();
+        let mut pending = VecDeque::new();
+
+        let (pending_tx, mut pending_rx) = oneshot::channel();
+        pending.push_back(pending_tx);
+
+        let (queued_tx, mut queued_rx) = oneshot::channel();
+        let head = RequestHeadType::Owned(Default::default());
+        tx.unbounded_send((head, crate::body::Body::empty(), queued_tx))
+            .unwrap();
+        drop(tx);
+
+        fail_pipelined_remaining(&mut rx, &mut pending);
+
+        assert!(pending.is_empty());
+        assert!(matches!(
+            pending_rx.try_recv(),
+            Ok(Some(Err(SendRequestError::Connect(ConnectError::Disconnected))))
+        ));
+        assert!(matches!(
+            queued_rx.try_recv(),
+            Ok(Some(Err(SendRequestError::Connect(ConnectError::Disconnected))))
+        ));
+    }
+
+    /// A flush that drained a completely-full buffer means the body is
+    /// producing faster than we're flushing: grow toward `max`.
+    #[test]
+    fn next_write_buffer_target_grows_when_buffer_was_full() {
+        assert_eq!(next_write_buffer_target(8 * 1024, 8 * 1024, true, 8 * 1024, 256 * 1024), 16 * 1024);
+    }
+
+    /// Growth is capped at `max`, never overshooting it.
+    #[test]
+    fn next_write_buffer_target_growth_is_capped_at_max() {
+        assert_eq!(next_write_buffer_target(200 * 1024, 200 * 1024, true, 8 * 1024, 256 * 1024), 256 * 1024);
+    }
+
+    /// A flush that drained a mostly-empty buffer means the target is
+    /// oversized for this body: shrink back toward `min`.
+    #[test]
+    fn next_write_buffer_target_shrinks_when_buffer_was_mostly_empty() {
+        assert_eq!(next_write_buffer_target(64 * 1024, 1024, false, 8 * 1024, 256 * 1024), 32 * 1024);
+    }
+
+    /// Shrinking is floored at `min`, never undershooting it.
+    #[test]
+    fn next_write_buffer_target_shrink_is_floored_at_min() {
+        assert_eq!(next_write_buffer_target(10 * 1024, 0, false, 8 * 1024, 256 * 1024), 8 * 1024);
+    }
+
+    /// Neither comfortably full nor mostly empty: hold the target steady.
+    #[test]
+    fn next_write_buffer_target_holds_steady_in_between() {
+        assert_eq!(next_write_buffer_target(64 * 1024, 40 * 1024, false, 8 * 1024, 256 * 1024), 64 * 1024);
+    }
+}
@@ -0,0 +1,1130 @@
+//! A yamux-style stream multiplexing layer over an upgraded tunnel.
+//!
+//! [`open_tunnel`](super::h1proto::open_tunnel) hands back a single, raw,
+//! bidirectional byte stream after a `CONNECT`. This module runs a small
+//! framed protocol over that stream so callers can open several independent
+//! logical substreams concurrently instead of being limited to exactly one
+//! — e.g. to tunnel several concurrent requests, each driving its own
+//! `H1Connection`, through a single proxied `CONNECT`.
+//!
+//! The framing mirrors yamux: a fixed 12-byte header (1-byte version,
+//! 1-byte frame type, 2-byte flags, 4-byte stream id, 4-byte length/value)
+//! optionally followed, for `Data` frames, by `length` bytes of payload.
+//! Stream ids are assigned odd on this (client) side so they can't collide
+//! with a peer opening streams of its own (which would use even ids).
+//! `WindowUpdate` frames grant the peer credit to send more `Data` on a
+//! stream, bounding how much a slow reader lets the peer put on the wire;
+//! `Ping` provides connection-level keepalive/RTT measurement; `GoAway`
+//! tells the peer (or is told by the peer) that no further streams will be
+//! accepted.
+
+use std::collections::{HashMap, VecDeque};
+use std::future::Future;
+use std::io;
+use std::marker::PhantomData;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+
+use actix_codec::{AsyncRead, AsyncWrite, ReadBuf};
+use bytes::buf::BufMut;
+use bytes::{Buf, Bytes, BytesMut};
+use futures_channel::{mpsc, oneshot};
+use futures_util::{
+    future::{poll_fn, select, Either},
+    StreamExt as _,
+};
+
+/// Multiplexer protocol version understood by this implementation.
+const VERSION: u8 = 0;
+
+/// Size, in bytes, of a frame header: 1 (version) + 1 (type) + 2 (flags) +
+/// 4 (stream id) + 4 (length/value).
+const HEADER_LEN: usize = 12;
+
+/// Receive window granted to the peer for each stream, in bytes: both how
+/// much we initially allow it to send on a newly opened stream, and how
+/// much credit we top it back up to once a reader has drained that much.
+const INITIAL_WINDOW: u32 = 256 * 1024;
+
+/// Bound on how many bytes of a stream's writes `MultiplexStream::poll_write`
+/// will hand off to the driver task (via `Command::Write`) before it's
+/// written onto the wire. Past this, `poll_write` applies backpressure
+/// instead of letting `pending_writes` grow without bound behind a peer
+/// that's slow to return credit. Matches `INITIAL_WINDOW`, the scale at
+/// which the peer is already expected to keep up.
+const MAX_QUEUED_WRITE_BYTES: usize = INITIAL_WINDOW as usize;
+
+/// Shared between a [`MultiplexStream`] and its [`StreamEntry`] in the
+/// driver task: how many bytes of that stream's writes are queued but not
+/// yet flushed onto the wire, and the waker to rouse once that count drops
+/// (or the stream closes).
+#[derive(Default)]
+struct WriteGate {
+    queued: usize,
+    closed: bool,
+    waker: Option<Waker>,
+}
+
+type SharedWriteGate = Arc<Mutex<WriteGate>>;
+
+/// Marks `gate` closed and wakes anyone blocked in `poll_write`, so a
+/// stream teardown (local or peer-initiated) doesn't leave a writer parked
+/// forever waiting for credit that will never come back.
+fn close_write_gate(gate: &SharedWriteGate) {
+    let mut gate = gate.lock().unwrap();
+    gate.closed = true;
+    if let Some(waker) = gate.waker.take() {
+        waker.wake();
+    }
+}
+
+/// Releases `n` bytes from `gate`'s queued count once the driver has handed
+/// them off to the wire, waking a parked `poll_write` if there's room again.
+fn release_queued_write_bytes(gate: &SharedWriteGate, n: usize) {
+    let mut gate = gate.lock().unwrap();
+    gate.queued = gate.queued.saturating_sub(n);
+    if let Some(waker) = gate.waker.take() {
+        waker.wake();
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FrameType {
+    Data,
+    WindowUpdate,
+    Ping,
+    GoAway,
+}
+
+impl FrameType {
+    fn from_u8(v: u8) -> io::Result<Self> {
+        match v {
+            0x0 => Ok(FrameType::Data),
+            0x1 => Ok(FrameType::WindowUpdate),
+            0x2 => Ok(FrameType::Ping),
+            0x3 => Ok(FrameType::GoAway),
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "unknown multiplexer frame type",
+            )),
+        }
+    }
+
+    fn as_u8(self) -> u8 {
+        match self {
+            FrameType::Data => 0x0,
+            FrameType::WindowUpdate => 0x1,
+            FrameType::Ping => 0x2,
+            FrameType::GoAway => 0x3,
+        }
+    }
+}
+
+mod flags {
+    /// Opens a new stream.
+    pub(super) const SYN: u16 = 0x1;
+    /// Acknowledges a new stream.
+    pub(super) const ACK: u16 = 0x2;
+    /// Half-closes the sender's side of a stream.
+    pub(super) const FIN: u16 = 0x4;
+    /// Immediately and ungracefully terminates a stream.
+    pub(super) const RST: u16 = 0x8;
+}
+
+#[derive(Debug, Clone, Copy)]
+struct FrameHeader {
+    typ: FrameType,
+    flags: u16,
+    stream_id: u32,
+    length: u32,
+}
+
+impl FrameHeader {
+    fn encode(&self, dst: &mut BytesMut) {
+        dst.put_u8(VERSION);
+        dst.put_u8(self.typ.as_u8());
+        dst.put_u16(self.flags);
+        dst.put_u32(self.stream_id);
+        dst.put_u32(self.length);
+    }
+
+    fn decode(src: &[u8; HEADER_LEN]) -> io::Result<Self> {
+        if src[0] != VERSION {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "unsupported multiplexer frame version",
+            ));
+        }
+
+        Ok(FrameHeader {
+            typ: FrameType::from_u8(src[1])?,
+            flags: u16::from_be_bytes([src[2], src[3]]),
+            stream_id: u32::from_be_bytes([src[4], src[5], src[6], src[7]]),
+            length: u32::from_be_bytes([src[8], src[9], src[10], src[11]]),
+        })
+    }
+}
+
+enum Command {
+    Open(oneshot::Sender<io::Result<MultiplexStream>>),
+    Write(u32, Bytes),
+    CreditReturn(u32, u32),
+    Close(u32),
+    Ping(oneshot::Sender<()>),
+}
+
+fn closed_err() -> io::Error {
+    io::Error::new(io::ErrorKind::NotConnected, "multiplexer driver is gone")
+}
+
+/// A cheaply-clonable handle for opening substreams over a connection
+/// established by [`multiplex`].
+#[derive(Clone)]
+pub struct Multiplexer {
+    cmd_tx: mpsc::UnboundedSender<Command>,
+}
+
+impl Multiplexer {
+    /// Open a new logical substream, returning once the peer has
+    /// acknowledged it.
+    pub async fn open_stream(&self) -> io::Result<MultiplexStream> {
+        let (tx, rx) = oneshot::channel();
+        self.cmd_tx
+            .unbounded_send(Command::Open(tx))
+            .map_err(|_| closed_err())?;
+        rx.await.map_err(|_| closed_err())?
+    }
+
+    /// Send a connection-level keepalive probe and wait for the peer's
+    /// acknowledgement, e.g. to measure round-trip time.
+    pub async fn ping(&self) -> io::Result<()> {
+        let (tx, rx) = oneshot::channel();
+        self.cmd_tx
+            .unbounded_send(Command::Ping(tx))
+            .map_err(|_| closed_err())?;
+        rx.await.map_err(|_| closed_err())
+    }
+}
+
+/// One logical substream opened by [`Multiplexer::open_stream`].
+///
+/// Implements `AsyncRead + AsyncWrite`, so it plugs back into
+/// `H1Connection` the same way a raw socket would, letting the existing
+/// client codec run per substream.
+pub struct MultiplexStream {
+    id: u32,
+    cmd_tx: mpsc::UnboundedSender<Command>,
+    data_rx: mpsc::UnboundedReceiver<Bytes>,
+    read_buf: Bytes,
+    eof: bool,
+    write_gate: SharedWriteGate,
+}
+
+impl Drop for MultiplexStream {
+    fn drop(&mut self) {
+        let _ = self.cmd_tx.unbounded_send(Command::Close(self.id));
+    }
+}
+
+impl AsyncRead for MultiplexStream {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+
+        if this.read_buf.is_empty() && !this.eof {
+            match this.data_rx.poll_next_unpin(cx) {
+                Poll::Ready(Some(chunk)) => this.read_buf = chunk,
+                Poll::Ready(None) => this.eof = true,
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+
+        let n = buf.remaining().min(this.read_buf.len());
+        if n > 0 {
+            buf.put_slice(&this.read_buf[..n]);
+            this.read_buf.advance(n);
+
+            // top the peer's window back up by what we just drained, so it
+            // can keep sending on this stream
+            let _ = this
+                .cmd_tx
+                .unbounded_send(Command::CreditReturn(this.id, n as u32));
+        }
+
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl AsyncWrite for MultiplexStream {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+
+        let mut gate = this.write_gate.lock().unwrap();
+        if gate.closed {
+            return Poll::Ready(Err(closed_err()));
+        }
+        if gate.queued >= MAX_QUEUED_WRITE_BYTES {
+            gate.waker = Some(cx.waker().clone());
+            return Poll::Pending;
+        }
+
+        let chunk = Bytes::copy_from_slice(buf);
+        let len = chunk.len();
+        gate.queued += len;
+        drop(gate);
+
+        this.cmd_tx
+            .unbounded_send(Command::Write(this.id, chunk))
+            .map_err(|_| closed_err())?;
+
+        Poll::Ready(Ok(len))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        let _ = this.cmd_tx.unbounded_send(Command::Close(this.id));
+        Poll::Ready(Ok(()))
+    }
+}
+
+/// Drives the multiplexing protocol for a connection established by
+/// [`multiplex`].
+///
+/// Must be polled to completion (typically spawned via `actix_rt::spawn`)
+/// for substreams to make progress. Resolves once the peer sends `GoAway`,
+/// the underlying connection is closed, or an unrecoverable framing error
+/// occurs.
+pub struct MultiplexerDriver<T> {
+    inner: Pin<Box<dyn Future<Output = io::Result<()>>>>,
+    _t: PhantomData<T>,
+}
+
+impl<T> Future for MultiplexerDriver<T> {
+    type Output = io::Result<()>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        self.inner.as_mut().poll(cx)
+    }
+}
+
+/// Runs a yamux-style multiplexing layer over `io`, typically the raw
+/// stream handed back by [`open_tunnel`](super::h1proto::open_tunnel) after
+/// a `CONNECT`.
+///
+/// Returns a cheaply-clonable [`Multiplexer`] handle for opening substreams
+/// and a [`MultiplexerDriver`] that must be polled (typically spawned via
+/// `actix_rt::spawn`) to read and write multiplexer frames; substreams make
+/// no progress until it is polled.
+pub fn multiplex<T>(io: T) -> (Multiplexer, MultiplexerDriver<T>)
+where
+    T: AsyncRead + AsyncWrite + Unpin + 'static,
+{
+    let (cmd_tx, cmd_rx) = mpsc::unbounded();
+
+    (
+        Multiplexer {
+            cmd_tx: cmd_tx.clone(),
+        },
+        MultiplexerDriver {
+            inner: Box::pin(drive_multiplexer(io, cmd_rx, cmd_tx)),
+            _t: PhantomData,
+        },
+    )
+}
+
+struct StreamEntry {
+    to_reader: mpsc::UnboundedSender<Bytes>,
+    send_credit: u32,
+    pending_writes: VecDeque<Bytes>,
+    /// How many more bytes of `Data` we've told the peer it may send on
+    /// this stream before it must wait for a `WindowUpdate`. Decremented as
+    /// `Data` frames arrive, topped back up (via [`Command::CreditReturn`])
+    /// as the local reader drains what it's already received. This is the
+    /// receive-side counterpart to `send_credit`, which throttles our own
+    /// writes against credit the peer granted us.
+    recv_window: u32,
+    /// Shared with the [`MultiplexStream`] half so `poll_write` can apply
+    /// backpressure once too much of this stream's data is queued here
+    /// unsent.
+    write_gate: SharedWriteGate,
+}
+
+async fn drive_multiplexer<T>(
+    mut io: T,
+    mut cmd_rx: mpsc::UnboundedReceiver<Command>,
+    cmd_tx: mpsc::UnboundedSender<Command>,
+) -> io::Result<()>
+where
+    T: AsyncRead + AsyncWrite + Unpin + 'static,
+{
+    let mut streams: HashMap<u32, StreamEntry> = HashMap::new();
+    let mut pending_opens: HashMap<
+        u32,
+        (MultiplexStream, oneshot::Sender<io::Result<MultiplexStream>>),
+    > = HashMap::new();
+    let mut pending_pings: VecDeque<oneshot::Sender<()>> = VecDeque::new();
+    // client-initiated streams use odd ids so they can't collide with a peer
+    // opening streams of its own (which would use even ids)
+    let mut next_stream_id: u32 = 1;
+
+    loop {
+        // handle every command already queued, back-to-back, before going
+        // back to listening for the next incoming frame
+        let mut idle = false;
+        while !idle {
+            match cmd_rx.try_next() {
+                Ok(Some(cmd)) => {
+                    handle_command(
+                        &mut io,
+                        cmd,
+                        &mut streams,
+                        &mut pending_opens,
+                        &mut pending_pings,
+                        &mut next_stream_id,
+                        &cmd_tx,
+                    )
+                    .await?
+                }
+                Ok(None) => {
+                    // every `Multiplexer` handle was dropped; once all
+                    // streams finish, there's nothing left to drive
+                    if streams.is_empty() && pending_opens.is_empty() {
+                        return Ok(());
+                    }
+                    idle = true;
+                }
+                Err(_) => idle = true,
+            }
+        }
+
+        let frame_fut = read_frame(&mut io);
+        actix_rt::pin!(frame_fut);
+
+        match select(cmd_rx.next(), frame_fut).await {
+            Either::Left((Some(cmd), _)) => {
+                handle_command(
+                    &mut io,
+                    cmd,
+                    &mut streams,
+                    &mut pending_opens,
+                    &mut pending_pings,
+                    &mut next_stream_id,
+                    &cmd_tx,
+                )
+                .await?
+            }
+            Either::Left((None, _)) => {
+                if streams.is_empty() && pending_opens.is_empty() {
+                    return Ok(());
+                }
+            }
+            Either::Right((frame, _)) => {
+                match frame? {
+                    Some((header, payload)) => {
+                        let go_away = header.typ == FrameType::GoAway;
+                        handle_frame(&mut io, header, payload, &mut streams, &mut pending_opens, &mut pending_pings).await?;
+                        if go_away {
+                            // `handle_frame` has already failed every stream
+                            return Ok(());
+                        }
+                    }
+                    None => {
+                        // peer went away without a `GoAway`; treat the same way
+                        fail_all(&mut streams, &mut pending_opens);
+                        return Ok(());
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn fail_all(
+    streams: &mut HashMap<u32, StreamEntry>,
+    pending_opens: &mut HashMap<u32, (MultiplexStream, oneshot::Sender<io::Result<MultiplexStream>>)>,
+) {
+    for (_, entry) in streams.drain() {
+        close_write_gate(&entry.write_gate);
+    }
+    for (_, (_, tx)) in pending_opens.drain() {
+        let _ = tx.send(Err(closed_err()));
+    }
+}
+
+async fn handle_command<T>(
+    io: &mut T,
+    cmd: Command,
+    streams: &mut HashMap<u32, StreamEntry>,
+    pending_opens: &mut HashMap<u32, (MultiplexStream, oneshot::Sender<io::Result<MultiplexStream>>)>,
+    pending_pings: &mut VecDeque<oneshot::Sender<()>>,
+    next_stream_id: &mut u32,
+    cmd_tx: &mpsc::UnboundedSender<Command>,
+) -> io::Result<()>
+where
+    T: AsyncWrite + Unpin,
+{
+    match cmd {
+        Command::Open(tx) => {
+            let id = *next_stream_id;
+            *next_stream_id += 2;
+
+            let (to_reader, data_rx) = mpsc::unbounded();
+            let write_gate: SharedWriteGate = Arc::new(Mutex::new(WriteGate::default()));
+            streams.insert(
+                id,
+                StreamEntry {
+                    to_reader,
+                    send_credit: INITIAL_WINDOW,
+                    pending_writes: VecDeque::new(),
+                    recv_window: INITIAL_WINDOW,
+                    write_gate: write_gate.clone(),
+                },
+            );
+
+            let stream = MultiplexStream {
+                id,
+                cmd_tx: cmd_tx.clone(),
+                data_rx,
+                read_buf: Bytes::new(),
+                eof: false,
+                write_gate,
+            };
+            pending_opens.insert(id, (stream, tx));
+
+            write_frame(
+                io,
+                FrameHeader {
+                    typ: FrameType::WindowUpdate,
+                    flags: flags::SYN,
+                    stream_id: id,
+                    length: INITIAL_WINDOW,
+                },
+                &[],
+            )
+            .await
+        }
+        Command::Write(id, data) => {
+            let entry = match streams.get_mut(&id) {
+                Some(entry) => entry,
+                None => return Ok(()), // stream already closed locally or by the peer
+            };
+
+            entry.pending_writes.push_back(data);
+            flush_pending_writes(io, id, entry).await
+        }
+        Command::CreditReturn(id, n) => {
+            // the local reader just drained `n` bytes: restore that much of
+            // the window we grant the peer to send on this stream before
+            // telling the peer about it
+            if let Some(entry) = streams.get_mut(&id) {
+                entry.recv_window = entry.recv_window.saturating_add(n);
+            }
+
+            write_frame(
+                io,
+                FrameHeader {
+                    typ: FrameType::WindowUpdate,
+                    flags: 0,
+                    stream_id: id,
+                    length: n,
+                },
+                &[],
+            )
+            .await
+        }
+        Command::Close(id) => {
+            if let Some(entry) = streams.remove(&id) {
+                close_write_gate(&entry.write_gate);
+            }
+            write_frame(
+                io,
+                FrameHeader {
+                    typ: FrameType::WindowUpdate,
+                    flags: flags::FIN,
+                    stream_id: id,
+                    length: 0,
+                },
+                &[],
+            )
+            .await
+        }
+        Command::Ping(tx) => {
+            pending_pings.push_back(tx);
+            write_frame(
+                io,
+                FrameHeader {
+                    typ: FrameType::Ping,
+                    flags: flags::SYN,
+                    stream_id: 0,
+                    length: pending_pings.len() as u32,
+                },
+                &[],
+            )
+            .await
+        }
+    }
+}
+
+/// Writes as much of `entry`'s queued data as the peer's granted window
+/// (`entry.send_credit`) currently allows, leaving the rest queued.
+async fn flush_pending_writes<T>(io: &mut T, id: u32, entry: &mut StreamEntry) -> io::Result<()>
+where
+    T: AsyncWrite + Unpin,
+{
+    while entry.send_credit > 0 {
+        let chunk = match entry.pending_writes.pop_front() {
+            Some(chunk) => chunk,
+            None => break,
+        };
+
+        let allowed = entry.send_credit as usize;
+        if chunk.len() <= allowed {
+            entry.send_credit -= chunk.len() as u32;
+            write_frame(
+                io,
+                FrameHeader {
+                    typ: FrameType::Data,
+                    flags: 0,
+                    stream_id: id,
+                    length: chunk.len() as u32,
+                },
+                &chunk,
+            )
+            .await?;
+            release_queued_write_bytes(&entry.write_gate, chunk.len());
+        } else {
+            let mut chunk = chunk;
+            let head = chunk.split_to(allowed);
+            entry.send_credit = 0;
+            write_frame(
+                io,
+                FrameHeader {
+                    typ: FrameType::Data,
+                    flags: 0,
+                    stream_id: id,
+                    length: head.len() as u32,
+                },
+                &head,
+            )
+            .await?;
+            release_queued_write_bytes(&entry.write_gate, head.len());
+            entry.pending_writes.push_front(chunk);
+            break;
+        }
+    }
+
+    Ok(())
+}
+
+async fn handle_frame<T>(
+    io: &mut T,
+    header: FrameHeader,
+    payload: Bytes,
+    streams: &mut HashMap<u32, StreamEntry>,
+    pending_opens: &mut HashMap<u32, (MultiplexStream, oneshot::Sender<io::Result<MultiplexStream>>)>,
+    pending_pings: &mut VecDeque<oneshot::Sender<()>>,
+) -> io::Result<()>
+where
+    T: AsyncWrite + Unpin,
+{
+    let id = header.stream_id;
+
+    if header.flags & flags::RST != 0 {
+        if let Some(entry) = streams.remove(&id) {
+            close_write_gate(&entry.write_gate);
+        }
+        if let Some((_, tx)) = pending_opens.remove(&id) {
+            let _ = tx.send(Err(io::Error::new(
+                io::ErrorKind::ConnectionReset,
+                "peer reset multiplexed stream",
+            )));
+        }
+        return Ok(());
+    }
+
+    if header.flags & flags::SYN != 0 && header.typ != FrameType::Ping {
+        // this minimal implementation only opens streams locally; reject
+        // streams the peer tries to open on us instead of silently
+        // dropping their data (a `Ping` also carries SYN, but opens no
+        // stream, and is answered below)
+        write_frame(
+            io,
+            FrameHeader {
+                typ: FrameType::WindowUpdate,
+                flags: flags::RST,
+                stream_id: id,
+                length: 0,
+            },
+            &[],
+        )
+        .await?;
+        return Ok(());
+    }
+
+    if header.flags & flags::ACK != 0 {
+        if let Some((stream, tx)) = pending_opens.remove(&id) {
+            let _ = tx.send(Ok(stream));
+        }
+    }
+
+    match header.typ {
+        FrameType::WindowUpdate => {
+            if let Some(entry) = streams.get_mut(&id) {
+                entry.send_credit = entry.send_credit.saturating_add(header.length);
+                flush_pending_writes(io, id, entry).await?;
+            }
+        }
+        FrameType::Data => {
+            if !payload.is_empty() {
+                match streams.get_mut(&id) {
+                    Some(entry) if payload.len() as u32 <= entry.recv_window => {
+                        entry.recv_window -= payload.len() as u32;
+                        let _ = entry.to_reader.unbounded_send(payload);
+                    }
+                    Some(_) => {
+                        // the peer sent more than the window we granted it on
+                        // this stream: a slow/absent local reader plus a peer
+                        // that ignores WindowUpdate pacing would otherwise
+                        // pile up unbounded data in `to_reader`. Tear down
+                        // just this stream, the same as an incoming RST,
+                        // rather than buffering past the window we advertised.
+                        if let Some(entry) = streams.remove(&id) {
+                            close_write_gate(&entry.write_gate);
+                        }
+                        write_frame(
+                            io,
+                            FrameHeader {
+                                typ: FrameType::WindowUpdate,
+                                flags: flags::RST,
+                                stream_id: id,
+                                length: 0,
+                            },
+                            &[],
+                        )
+                        .await?;
+                    }
+                    None => {}
+                }
+            }
+        }
+        FrameType::Ping => {
+            if header.flags & flags::ACK != 0 {
+                if let Some(tx) = pending_pings.pop_front() {
+                    let _ = tx.send(());
+                }
+            } else {
+                write_frame(
+                    io,
+                    FrameHeader {
+                        typ: FrameType::Ping,
+                        flags: flags::ACK,
+                        stream_id: 0,
+                        length: header.length,
+                    },
+                    &[],
+                )
+                .await?;
+            }
+        }
+        FrameType::GoAway => {
+            fail_all(streams, pending_opens);
+        }
+    }
+
+    if header.flags & flags::FIN != 0 {
+        // dropping the sender signals end-of-stream to the reader half;
+        // the local write half is closed along with it
+        if let Some(entry) = streams.remove(&id) {
+            close_write_gate(&entry.write_gate);
+        }
+    }
+
+    Ok(())
+}
+
+async fn write_frame<T>(io: &mut T, header: FrameHeader, payload: &[u8]) -> io::Result<()>
+where
+    T: AsyncWrite + Unpin,
+{
+    let mut buf = BytesMut::with_capacity(HEADER_LEN + payload.len());
+    header.encode(&mut buf);
+    buf.extend_from_slice(payload);
+    write_all(io, &buf).await
+}
+
+/// Reads one frame (header plus payload, if any) from `io`, or `Ok(None)`
+/// if the peer closed the connection cleanly between frames.
+async fn read_frame<T>(io: &mut T) -> io::Result<Option<(FrameHeader, Bytes)>>
+where
+    T: AsyncRead + Unpin,
+{
+    let mut header_buf = [0u8; HEADER_LEN];
+    if !read_exact_or_eof(io, &mut header_buf).await? {
+        return Ok(None);
+    }
+
+    let header = FrameHeader::decode(&header_buf)?;
+
+    let payload = if header.typ == FrameType::Data && header.length > 0 {
+        let mut buf = BytesMut::zeroed(header.length as usize);
+        read_exact(io, &mut buf).await?;
+        buf.freeze()
+    } else {
+        Bytes::new()
+    };
+
+    Ok(Some((header, payload)))
+}
+
+/// Reads exactly `buf.len()` bytes, or an `UnexpectedEof` error if the
+/// connection closes first.
+async fn read_exact<T: AsyncRead + Unpin>(io: &mut T, buf: &mut [u8]) -> io::Result<()> {
+    if !read_exact_or_eof(io, buf).await? {
+        return Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            "multiplexer connection closed mid-frame",
+        ));
+    }
+
+    Ok(())
+}
+
+/// Reads exactly `buf.len()` bytes, returning `Ok(false)` if the peer closed
+/// the connection before any bytes were read (a clean shutdown between
+/// frames), or an `UnexpectedEof` error if it closed mid-frame.
+async fn read_exact_or_eof<T: AsyncRead + Unpin>(io: &mut T, buf: &mut [u8]) -> io::Result<bool> {
+    let mut read = 0;
+
+    while read < buf.len() {
+        let n = poll_fn(|cx| {
+            let mut read_buf = ReadBuf::new(&mut buf[read..]);
+            match Pin::new(&mut *io).poll_read(cx, &mut read_buf) {
+                Poll::Ready(Ok(())) => Poll::Ready(Ok(read_buf.filled().len())),
+                Poll::Ready(Err(e)) => Poll::Ready(Err(e)),
+                Poll::Pending => Poll::Pending,
+            }
+        })
+        .await?;
+
+        if n == 0 {
+            return if read == 0 {
+                Ok(false)
+            } else {
+                Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    "multiplexer connection closed mid-frame",
+                ))
+            };
+        }
+
+        read += n;
+    }
+
+    Ok(true)
+}
+
+async fn write_all<T: AsyncWrite + Unpin>(io: &mut T, mut buf: &[u8]) -> io::Result<()> {
+    while !buf.is_empty() {
+        let n = poll_fn(|cx| Pin::new(&mut *io).poll_write(cx, buf)).await?;
+        if n == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::WriteZero,
+                "failed to write multiplexer frame",
+            ));
+        }
+        buf = &buf[n..];
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::task::{Context, Poll};
+
+    use super::*;
+
+    /// An in-memory sink standing in for the multiplexed connection, so
+    /// `handle_frame`'s writes (window updates, RSTs, pings) can be
+    /// inspected without a real socket.
+    struct MockIo {
+        written: Vec<u8>,
+    }
+
+    impl AsyncWrite for MockIo {
+        fn poll_write(
+            mut self: Pin<&mut Self>,
+            _cx: &mut Context<'_>,
+            buf: &[u8],
+        ) -> Poll<io::Result<usize>> {
+            self.written.extend_from_slice(buf);
+            Poll::Ready(Ok(buf.len()))
+        }
+
+        fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn poll_shutdown(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    fn open_entry(streams: &mut HashMap<u32, StreamEntry>, id: u32, recv_window: u32) {
+        let (to_reader, _data_rx) = mpsc::unbounded();
+        streams.insert(
+            id,
+            StreamEntry {
+                to_reader,
+                send_credit: INITIAL_WINDOW,
+                pending_writes: VecDeque::new(),
+                recv_window,
+                write_gate: Arc::new(Mutex::new(WriteGate::default())),
+            },
+        );
+    }
+
+    fn last_written_header(io: &MockIo) -> FrameHeader {
+        let mut header_bytes = [0u8; HEADER_LEN];
+        let start = io.written.len() - HEADER_LEN;
+        header_bytes.copy_from_slice(&io.written[start..]);
+        FrameHeader::decode(&header_bytes).unwrap()
+    }
+
+    #[actix_rt::test]
+    async fn data_within_window_is_forwarded_and_decrements_window() {
+        let mut io = MockIo { written: Vec::new() };
+        let mut streams = HashMap::new();
+        open_entry(&mut streams, 1, 10);
+        let mut pending_opens = HashMap::new();
+        let mut pending_pings = VecDeque::new();
+
+        let header = FrameHeader {
+            typ: FrameType::Data,
+            flags: 0,
+            stream_id: 1,
+            length: 4,
+        };
+
+        handle_frame(
+            &mut io,
+            header,
+            Bytes::from_static(b"data"),
+            &mut streams,
+            &mut pending_opens,
+            &mut pending_pings,
+        )
+        .await
+        .unwrap();
+
+        let entry = streams.get(&1).expect("stream stays open");
+        assert_eq!(entry.recv_window, 6);
+        assert!(io.written.is_empty(), "no reply frame expected");
+    }
+
+    #[actix_rt::test]
+    async fn data_exceeding_window_resets_the_stream() {
+        let mut io = MockIo { written: Vec::new() };
+        let mut streams = HashMap::new();
+        open_entry(&mut streams, 1, 4);
+        let mut pending_opens = HashMap::new();
+        let mut pending_pings = VecDeque::new();
+
+        let header = FrameHeader {
+            typ: FrameType::Data,
+            flags: 0,
+            stream_id: 1,
+            length: 10,
+        };
+
+        handle_frame(
+            &mut io,
+            header,
+            Bytes::from_static(b"0123456789"),
+            &mut streams,
+            &mut pending_opens,
+            &mut pending_pings,
+        )
+        .await
+        .unwrap();
+
+        assert!(
+            !streams.contains_key(&1),
+            "stream over its granted window must be torn down"
+        );
+
+        let reply = last_written_header(&io);
+        assert_eq!(reply.typ, FrameType::WindowUpdate);
+        assert_eq!(reply.flags & flags::RST, flags::RST);
+        assert_eq!(reply.stream_id, 1);
+    }
+
+    #[actix_rt::test]
+    async fn credit_return_tops_up_recv_window() {
+        let mut streams = HashMap::new();
+        open_entry(&mut streams, 1, 4);
+        let (cmd_tx, _cmd_rx) = mpsc::unbounded();
+        let mut io = MockIo { written: Vec::new() };
+        let mut pending_opens = HashMap::new();
+        let mut pending_pings = VecDeque::new();
+        let mut next_stream_id = 3;
+
+        handle_command(
+            &mut io,
+            Command::CreditReturn(1, 6),
+            &mut streams,
+            &mut pending_opens,
+            &mut pending_pings,
+            &mut next_stream_id,
+            &cmd_tx,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(streams.get(&1).unwrap().recv_window, 10);
+    }
+
+    #[actix_rt::test]
+    async fn fin_closes_the_stream() {
+        let mut io = MockIo { written: Vec::new() };
+        let mut streams = HashMap::new();
+        open_entry(&mut streams, 1, INITIAL_WINDOW);
+        let mut pending_opens = HashMap::new();
+        let mut pending_pings = VecDeque::new();
+
+        let header = FrameHeader {
+            typ: FrameType::WindowUpdate,
+            flags: flags::FIN,
+            stream_id: 1,
+            length: 0,
+        };
+
+        handle_frame(
+            &mut io,
+            header,
+            Bytes::new(),
+            &mut streams,
+            &mut pending_opens,
+            &mut pending_pings,
+        )
+        .await
+        .unwrap();
+
+        assert!(!streams.contains_key(&1));
+    }
+
+    fn noop_waker() -> Waker {
+        use std::task::{RawWaker, RawWakerVTable};
+
+        fn clone(_: *const ()) -> RawWaker {
+            raw()
+        }
+        fn no_op(_: *const ()) {}
+        fn raw() -> RawWaker {
+            RawWaker::new(std::ptr::null(), &RawWakerVTable::new(clone, no_op, no_op, no_op))
+        }
+
+        unsafe { Waker::from_raw(raw()) }
+    }
+
+    fn open_stream(write_gate: SharedWriteGate) -> (MultiplexStream, mpsc::UnboundedReceiver<Command>) {
+        let (cmd_tx, cmd_rx) = mpsc::unbounded();
+        let (_to_reader, data_rx) = mpsc::unbounded();
+        let stream = MultiplexStream {
+            id: 1,
+            cmd_tx,
+            data_rx,
+            read_buf: Bytes::new(),
+            eof: false,
+            write_gate,
+        };
+        (stream, cmd_rx)
+    }
+
+    /// Once a stream's queued-but-unflushed writes reach
+    /// `MAX_QUEUED_WRITE_BYTES`, `poll_write` must stop accepting more
+    /// instead of letting the driver's `pending_writes` grow without bound;
+    /// releasing queued bytes (as the driver does once it's flushed them to
+    /// the wire) wakes the writer back up.
+    #[test]
+    fn poll_write_applies_backpressure_once_queued_bytes_reach_the_cap() {
+        let write_gate: SharedWriteGate = Arc::new(Mutex::new(WriteGate::default()));
+        let (mut stream, mut cmd_rx) = open_stream(write_gate.clone());
+
+        let waker = noop_waker();
+        let mut cx = Context::from_waker(&waker);
+
+        let chunk = vec![0u8; MAX_QUEUED_WRITE_BYTES];
+        match Pin::new(&mut stream).poll_write(&mut cx, &chunk) {
+            Poll::Ready(Ok(n)) => assert_eq!(n, MAX_QUEUED_WRITE_BYTES),
+            other => panic!("expected immediate accept, got {:?}", other),
+        }
+        assert!(matches!(cmd_rx.try_next(), Ok(Some(Command::Write(1, _)))));
+
+        // the gate is now full: further writes must block rather than grow
+        // the driver's queue without bound
+        assert!(Pin::new(&mut stream).poll_write(&mut cx, b"more").is_pending());
+        assert!(
+            cmd_rx.try_next().is_err(),
+            "a blocked write must not reach the driver"
+        );
+
+        // once the driver reports those bytes flushed, the writer unblocks
+        release_queued_write_bytes(&write_gate, MAX_QUEUED_WRITE_BYTES);
+        match Pin::new(&mut stream).poll_write(&mut cx, b"more") {
+            Poll::Ready(Ok(n)) => assert_eq!(n, 4),
+            other => panic!("expected accept after credit returned, got {:?}", other),
+        }
+    }
+
+    /// A closed gate (stream torn down locally or by the peer) must fail
+    /// outstanding writes instead of leaving them parked forever.
+    #[test]
+    fn poll_write_fails_once_the_gate_is_closed() {
+        let write_gate: SharedWriteGate = Arc::new(Mutex::new(WriteGate::default()));
+        let (mut stream, _cmd_rx) = open_stream(write_gate.clone());
+
+        close_write_gate(&write_gate);
+
+        let waker = noop_waker();
+        let mut cx = Context::from_waker(&waker);
+        match Pin::new(&mut stream).poll_write(&mut cx, b"x") {
+            Poll::Ready(Err(e)) => assert_eq!(e.kind(), io::ErrorKind::NotConnected),
+            other => panic!("expected error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn frame_header_roundtrips_through_encode_decode() {
+        let header = FrameHeader {
+            typ: FrameType::Data,
+            flags: flags::SYN | flags::ACK,
+            stream_id: 7,
+            length: 42,
+        };
+
+        let mut buf = BytesMut::new();
+        header.encode(&mut buf);
+
+        let mut raw = [0u8; HEADER_LEN];
+        raw.copy_from_slice(&buf[..HEADER_LEN]);
+        let decoded = FrameHeader::decode(&raw).unwrap();
+
+        assert_eq!(decoded.typ, header.typ);
+        assert_eq!(decoded.flags, header.flags);
+        assert_eq!(decoded.stream_id, header.stream_id);
+        assert_eq!(decoded.length, header.length);
+    }
+}
@@ -0,0 +1,617 @@
+//! HTTP/1 wire codec shared by the client connection types in
+//! [`crate::client`].
+//!
+//! [`ClientCodec`] encodes request heads/bodies and decodes response heads;
+//! [`ClientPayloadCodec`] (obtained via [`ClientCodec::into_payload_codec`])
+//! takes over decoding the response body once the head has been read.
+
+use std::convert::TryFrom;
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use actix_codec::{Decoder, Encoder};
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use futures_core::Stream;
+
+use crate::error::{ParseError, PayloadError};
+use crate::header::HeaderMap;
+use crate::http::{StatusCode, Version};
+use crate::message::{RequestHeadType, ResponseHead};
+
+const MAX_HEADERS: usize = 96;
+
+/// Parser leniency toggles for the response status line and headers read by
+/// [`ClientCodec`], mirroring `httparse::ParserConfig`.
+///
+/// Every toggle defaults to strict RFC 7230 behavior. Enabling one lets a
+/// client talk to a non-conformant upstream instead of hard-failing with a
+/// `ParseError`: the decode path feeding `ResponseHead` normalizes the
+/// offending bytes before parsing continues.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct ParserConfig {
+    /// Tolerate whitespace between a header name and its colon.
+    pub allow_space_before_colon: bool,
+    /// Collapse obsolete multi-line ("folded") header continuation lines
+    /// into a single value instead of rejecting them.
+    pub allow_obsolete_line_folding: bool,
+    /// Tolerate a spurious leading newline before the status line.
+    pub allow_spurious_leading_newline: bool,
+}
+
+/// An outgoing request head/body chunk, or an already-decoded response head.
+///
+/// `Item` carries whatever the codec is being used to encode (a request head
+/// paired with its body's [`BodySize`](crate::body::BodySize)); `Chunk` is a
+/// request body chunk, with `None` marking the end of the body.
+pub(crate) enum Message<T> {
+    Item(T),
+    Chunk(Option<Bytes>),
+}
+
+impl<T> From<T> for Message<T> {
+    fn from(item: T) -> Self {
+        Message::Item(item)
+    }
+}
+
+/// How the just-decoded response's body is framed on the wire.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum MessageType {
+    /// No body follows (e.g. `204 No Content`, a response to `HEAD`).
+    None,
+    /// A body follows; see [`ClientCodec::into_payload_codec`].
+    Payload,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum PayloadLength {
+    None,
+    Length(u64),
+    Chunked,
+    /// No `Content-Length` or `Transfer-Encoding`: the body runs until the
+    /// connection closes.
+    CloseDelimited,
+}
+
+/// Lower bound for [`ClientCodec::write_buffer_bounds`]'s default: trickling
+/// bodies (and idle keep-alive connections between requests) settle here
+/// rather than holding onto a larger buffer than they need.
+const DEFAULT_MIN_WRITE_BUFFER_SIZE: usize = 8 * 1024;
+
+/// Upper bound for [`ClientCodec::write_buffer_bounds`]'s default: bodies
+/// that keep saturating the buffer between flushes grow toward this,
+/// trading memory for fewer, larger writes to the socket.
+const DEFAULT_MAX_WRITE_BUFFER_SIZE: usize = 256 * 1024;
+
+/// Client-side HTTP/1 codec: encodes requests and decodes response heads.
+///
+/// Leniency toggled on via `config` (see [`ParserConfig`]) is applied to the
+/// status line and header block while decoding; everything else follows
+/// strict RFC 7230 framing rules.
+pub(crate) struct ClientCodec {
+    config: ParserConfig,
+    head_parsed: Option<DecodedHead>,
+    write_buffer_min: usize,
+    write_buffer_max: usize,
+}
+
+#[derive(Clone, Copy)]
+struct DecodedHead {
+    version: Version,
+    keepalive: bool,
+    length: PayloadLengthKind,
+}
+
+#[derive(Clone, Copy)]
+enum PayloadLengthKind {
+    None,
+    Length(u64),
+    Chunked,
+    Close,
+}
+
+impl ClientCodec {
+    /// Builds a codec applying `config`'s leniency toggles to response
+    /// decoding.
+    pub(crate) fn with_config(config: ParserConfig) -> Self {
+        ClientCodec {
+            config,
+            head_parsed: None,
+            write_buffer_min: DEFAULT_MIN_WRITE_BUFFER_SIZE,
+            write_buffer_max: DEFAULT_MAX_WRITE_BUFFER_SIZE,
+        }
+    }
+
+    /// Overrides the bounds [`send_body`](super::client::h1proto::send_body)
+    /// adapts its write-buffer target within, in place of
+    /// [`DEFAULT_MIN_WRITE_BUFFER_SIZE`]/[`DEFAULT_MAX_WRITE_BUFFER_SIZE`].
+    pub fn with_write_buffer_bounds(mut self, min: usize, max: usize) -> Self {
+        // `send_body` starts its target at `min` and only ever grows it by
+        // doubling, so a `min` of 0 would leave the target stuck at 0
+        // forever — spinning on zero-sized writes instead of buffering
+        // anything.
+        self.write_buffer_min = min.max(1);
+        self.write_buffer_max = max.max(self.write_buffer_min);
+        self
+    }
+
+    /// The `(min, max)` bounds the adaptive write-buffer target in
+    /// [`send_body`](super::client::h1proto::send_body) grows and shrinks
+    /// within.
+    pub(crate) fn write_buffer_bounds(&self) -> (usize, usize) {
+        (self.write_buffer_min, self.write_buffer_max)
+    }
+
+    /// Whether the connection that produced the last decoded response may
+    /// carry another request, per the response's HTTP version and
+    /// `Connection` header.
+    pub(crate) fn keepalive(&self) -> bool {
+        self.head_parsed.map(|h| h.keepalive).unwrap_or(true)
+    }
+
+    /// How the last decoded response's body is framed.
+    pub(crate) fn message_type(&self) -> MessageType {
+        match self.head_parsed.map(|h| h.length) {
+            Some(PayloadLengthKind::None) | None => MessageType::None,
+            Some(_) => MessageType::Payload,
+        }
+    }
+
+    /// Whether the last decoded response's body has no delimiter of its own
+    /// (no `Content-Length`, not chunked) and so can only be read to
+    /// completion by reading until the connection closes.
+    ///
+    /// A connection that just produced one of these can't safely carry
+    /// another pipelined request/response behind it: the next response's
+    /// bytes would be indistinguishable from more of this one's body.
+    pub(crate) fn has_indeterminate_framing(&self) -> bool {
+        matches!(
+            self.head_parsed.map(|h| h.length),
+            Some(PayloadLengthKind::Close)
+        )
+    }
+
+    /// Converts this codec into one that decodes the body belonging to the
+    /// last-decoded head.
+    pub(crate) fn into_payload_codec(self) -> ClientPayloadCodec {
+        let head = self.head_parsed.unwrap_or(DecodedHead {
+            version: Version::HTTP_11,
+            keepalive: false,
+            length: PayloadLengthKind::None,
+        });
+
+        ClientPayloadCodec {
+            keepalive: head.keepalive,
+            length: match head.length {
+                PayloadLengthKind::None => PayloadLength::None,
+                PayloadLengthKind::Length(n) => PayloadLength::Length(n),
+                PayloadLengthKind::Chunked => PayloadLength::Chunked,
+                PayloadLengthKind::Close => PayloadLength::CloseDelimited,
+            },
+            chunked: ChunkedState::Size,
+            eof: false,
+        }
+    }
+}
+
+impl Decoder for ClientCodec {
+    type Item = ResponseHead;
+    type Error = ParseError;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        if self.config.allow_spurious_leading_newline {
+            while src.starts_with(b"\r\n") {
+                src.advance(2);
+            }
+            while src.first() == Some(&b'\n') {
+                src.advance(1);
+            }
+        } else if matches!(src.first(), Some(b'\r') | Some(b'\n')) {
+            // httparse would skip leading empty lines on its own; strict
+            // mode rejects them instead
+            return Err(ParseError::Status);
+        }
+
+        let mut headers_buf = [httparse::EMPTY_HEADER; MAX_HEADERS];
+        let mut parser = httparse::Response::new(&mut headers_buf);
+
+        let mut parser_config = httparse::ParserConfig::default();
+        parser_config
+            .allow_spaces_after_header_name_in_responses(self.config.allow_space_before_colon);
+        parser_config.allow_obsolete_multiline_headers_in_responses(
+            self.config.allow_obsolete_line_folding,
+        );
+
+        let status = parser_config
+            .parse_response(&mut parser, src)
+            .map_err(ParseError::from_httparse)?;
+
+        let consumed = match status {
+            httparse::Status::Complete(n) => n,
+            httparse::Status::Partial => return Ok(None),
+        };
+
+        let version = match parser.version {
+            Some(1) => Version::HTTP_11,
+            Some(0) => Version::HTTP_10,
+            _ => return Err(ParseError::Version),
+        };
+
+        let status_code = StatusCode::from_u16(parser.code.unwrap_or(0))
+            .map_err(|_| ParseError::Status)?;
+
+        let mut headers = HeaderMap::with_capacity(parser.headers.len());
+        for header in parser.headers.iter() {
+            let name = crate::http::header::HeaderName::try_from(header.name)
+                .map_err(|_| ParseError::Header)?;
+            // httparse leaves folded continuation lines in the value as-is;
+            // collapse each line break and its leading whitespace into one
+            // space
+            let mut folded = Vec::new();
+            let raw_value = if self.config.allow_obsolete_line_folding
+                && header.value.contains(&b'\n')
+            {
+                for line in header.value.split(|&b| b == b'\n') {
+                    let line = line.strip_suffix(b"\r").unwrap_or(line);
+                    let start = line
+                        .iter()
+                        .position(|&b| b != b' ' && b != b'\t')
+                        .unwrap_or(line.len());
+                    if !folded.is_empty() {
+                        folded.push(b' ');
+                    }
+                    folded.extend_from_slice(&line[start..]);
+                }
+                &folded[..]
+            } else {
+                header.value
+            };
+            let value = crate::http::header::HeaderValue::try_from(raw_value)
+                .map_err(|_| ParseError::Header)?;
+            headers.append(name, value);
+        }
+
+        let explicit_close = headers
+            .get(crate::http::header::CONNECTION)
+            .and_then(|v| v.to_str().ok())
+            .map(|v| v.eq_ignore_ascii_case("close"))
+            .unwrap_or(false);
+        let explicit_keepalive = headers
+            .get(crate::http::header::CONNECTION)
+            .and_then(|v| v.to_str().ok())
+            .map(|v| v.eq_ignore_ascii_case("keep-alive"))
+            .unwrap_or(false);
+
+        let keepalive = if explicit_close {
+            false
+        } else if explicit_keepalive {
+            true
+        } else {
+            version == Version::HTTP_11
+        };
+
+        let length = if status_code == StatusCode::NO_CONTENT
+            || status_code == StatusCode::NOT_MODIFIED
+            || status_code.as_u16() < 200
+        {
+            PayloadLengthKind::None
+        } else if headers
+            .get(crate::http::header::TRANSFER_ENCODING)
+            .and_then(|v| v.to_str().ok())
+            .map(|v| v.eq_ignore_ascii_case("chunked"))
+            .unwrap_or(false)
+        {
+            PayloadLengthKind::Chunked
+        } else if let Some(len) = headers.get(crate::http::header::CONTENT_LENGTH) {
+            let len: u64 = len
+                .to_str()
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .ok_or(ParseError::Header)?;
+            if len == 0 {
+                PayloadLengthKind::None
+            } else {
+                PayloadLengthKind::Length(len)
+            }
+        } else {
+            PayloadLengthKind::Close
+        };
+
+        self.head_parsed = Some(DecodedHead {
+            version,
+            keepalive,
+            length,
+        });
+
+        src.advance(consumed);
+
+        Ok(Some(ResponseHead {
+            version,
+            status: status_code,
+            headers,
+            reason: None,
+        }))
+    }
+}
+
+impl Encoder<Message<(RequestHeadType, crate::body::BodySize)>> for ClientCodec {
+    type Error = io::Error;
+
+    fn encode(
+        &mut self,
+        item: Message<(RequestHeadType, crate::body::BodySize)>,
+        dst: &mut BytesMut,
+    ) -> Result<(), Self::Error> {
+        match item {
+            Message::Item((head, body_size)) => {
+                let head_ref = head.as_ref();
+
+                dst.extend_from_slice(head_ref.method.as_str().as_bytes());
+                dst.put_u8(b' ');
+                dst.extend_from_slice(
+                    head_ref
+                        .uri
+                        .path_and_query()
+                        .map(|p| p.as_str())
+                        .unwrap_or("/")
+                        .as_bytes(),
+                );
+                dst.extend_from_slice(b" HTTP/1.1\r\n");
+
+                for (name, value) in head_ref.headers.iter() {
+                    dst.extend_from_slice(name.as_str().as_bytes());
+                    dst.extend_from_slice(b": ");
+                    dst.extend_from_slice(value.as_bytes());
+                    dst.extend_from_slice(b"\r\n");
+                }
+
+                for extra in head.extra_headers().iter() {
+                    for (name, value) in extra.iter() {
+                        dst.extend_from_slice(name.as_str().as_bytes());
+                        dst.extend_from_slice(b": ");
+                        dst.extend_from_slice(value.as_bytes());
+                        dst.extend_from_slice(b"\r\n");
+                    }
+                }
+
+                if matches!(body_size, crate::body::BodySize::Stream) {
+                    dst.extend_from_slice(b"transfer-encoding: chunked\r\n");
+                }
+
+                dst.extend_from_slice(b"\r\n");
+            }
+            Message::Chunk(Some(chunk)) => {
+                dst.reserve(chunk.len());
+                dst.extend_from_slice(&chunk);
+            }
+            Message::Chunk(None) => {}
+        }
+
+        Ok(())
+    }
+}
+
+/// Decodes a response body whose framing (`Content-Length`, chunked, or
+/// close-delimited) was determined while decoding the head via
+/// [`ClientCodec`].
+pub(crate) struct ClientPayloadCodec {
+    keepalive: bool,
+    length: PayloadLength,
+    chunked: ChunkedState,
+    eof: bool,
+}
+
+impl ClientPayloadCodec {
+    pub(crate) fn keepalive(&self) -> bool {
+        self.keepalive
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum ChunkedState {
+    Size,
+    Body(u64),
+    BodyEnd,
+    Trailer,
+    Done,
+}
+
+impl Decoder for ClientPayloadCodec {
+    /// `None` marks the end of the body.
+    type Item = Option<Bytes>;
+    type Error = PayloadError;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        if self.eof {
+            return Ok(Some(None));
+        }
+
+        match self.length {
+            PayloadLength::None => {
+                self.eof = true;
+                Ok(Some(None))
+            }
+            PayloadLength::Length(ref mut remaining) => {
+                if src.is_empty() {
+                    return Ok(None);
+                }
+                let n = (src.len() as u64).min(*remaining) as usize;
+                let chunk = src.split_to(n).freeze();
+                *remaining -= n as u64;
+                if *remaining == 0 {
+                    self.eof = true;
+                }
+                Ok(Some(Some(chunk)))
+            }
+            PayloadLength::CloseDelimited => {
+                if src.is_empty() {
+                    return Ok(None);
+                }
+                Ok(Some(Some(src.split().freeze())))
+            }
+            PayloadLength::Chunked => self.decode_chunked(src),
+        }
+    }
+}
+
+impl ClientPayloadCodec {
+    fn decode_chunked(
+        &mut self,
+        src: &mut BytesMut,
+    ) -> Result<Option<Option<Bytes>>, PayloadError> {
+        loop {
+            match self.chunked {
+                ChunkedState::Size => {
+                    let pos = match find_crlf(src) {
+                        Some(pos) => pos,
+                        None => return Ok(None),
+                    };
+                    let line = src.split_to(pos + 2);
+                    let line = &line[..line.len() - 2];
+                    let size_str = std::str::from_utf8(line)
+                        .ok()
+                        .and_then(|s| s.split(';').next())
+                        .ok_or(PayloadError::EncodingCorrupted)?;
+                    let size = u64::from_str_radix(size_str.trim(), 16)
+                        .map_err(|_| PayloadError::EncodingCorrupted)?;
+
+                    self.chunked = if size == 0 {
+                        ChunkedState::Trailer
+                    } else {
+                        ChunkedState::Body(size)
+                    };
+                }
+                ChunkedState::Body(remaining) => {
+                    if src.is_empty() {
+                        return Ok(None);
+                    }
+                    let n = (src.len() as u64).min(remaining) as usize;
+                    let chunk = src.split_to(n).freeze();
+                    let remaining = remaining - n as u64;
+
+                    self.chunked = if remaining == 0 {
+                        ChunkedState::BodyEnd
+                    } else {
+                        ChunkedState::Body(remaining)
+                    };
+
+                    return Ok(Some(Some(chunk)));
+                }
+                // consume the CRLF that terminates every chunk's data before
+                // parsing the next chunk's size line
+                ChunkedState::BodyEnd => {
+                    if src.len() < 2 {
+                        return Ok(None);
+                    }
+                    src.advance(2);
+                    self.chunked = ChunkedState::Size;
+                }
+                ChunkedState::Trailer => {
+                    let pos = match find_crlf(src) {
+                        Some(pos) => pos,
+                        None => return Ok(None),
+                    };
+
+                    if pos == 0 {
+                        src.advance(2);
+                        self.chunked = ChunkedState::Done;
+                        self.eof = true;
+                        return Ok(Some(None));
+                    }
+
+                    src.advance(pos + 2);
+                }
+                ChunkedState::Done => {
+                    self.eof = true;
+                    return Ok(Some(None));
+                }
+            }
+        }
+    }
+}
+
+fn find_crlf(src: &BytesMut) -> Option<usize> {
+    src.windows(2).position(|w| w == b"\r\n")
+}
+
+/// Extension exposing [`Stream::poll_next`] under the name `next_item`, for
+/// readability at call sites that drive a payload [`actix_codec::Framed`] by
+/// hand, one decoded item at a time. It yields the stream's own item type
+/// unchanged, `Result` wrapping included.
+pub(crate) trait NextItem: Stream {
+    fn next_item(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<<Self as Stream>::Item>> {
+        self.poll_next(cx)
+    }
+}
+
+impl<S: Stream> NextItem for S {}
+
+#[cfg(test)]
+mod tests {
+    use bytes::BytesMut;
+
+    use super::*;
+
+    /// Strict config rejects a header name followed by whitespace before its
+    /// colon; `allow_space_before_colon` lets it through instead.
+    #[test]
+    fn allow_space_before_colon_toggles_decode_outcome() {
+        let raw = &b"HTTP/1.1 200 OK\r\nFoo : bar\r\n\r\n"[..];
+
+        let mut strict = ClientCodec::with_config(ParserConfig::default());
+        assert!(strict.decode(&mut BytesMut::from(raw)).is_err());
+
+        let mut lenient = ClientCodec::with_config(ParserConfig {
+            allow_space_before_colon: true,
+            ..Default::default()
+        });
+        let head = lenient
+            .decode(&mut BytesMut::from(raw))
+            .unwrap()
+            .expect("head decoded");
+        assert_eq!(
+            head.headers.get("foo").unwrap().to_str().unwrap(),
+            "bar"
+        );
+    }
+
+    /// Strict config rejects an obsolete folded header continuation line;
+    /// `allow_obsolete_line_folding` lets it through instead.
+    #[test]
+    fn allow_obsolete_line_folding_toggles_decode_outcome() {
+        let raw = &b"HTTP/1.1 200 OK\r\nFoo: bar\r\n baz\r\n\r\n"[..];
+
+        let mut strict = ClientCodec::with_config(ParserConfig::default());
+        assert!(strict.decode(&mut BytesMut::from(raw)).is_err());
+
+        let mut lenient = ClientCodec::with_config(ParserConfig {
+            allow_obsolete_line_folding: true,
+            ..Default::default()
+        });
+        assert!(lenient.decode(&mut BytesMut::from(raw)).unwrap().is_some());
+    }
+
+    /// Strict config rejects a spurious leading newline before the status
+    /// line; `allow_spurious_leading_newline` strips it instead.
+    #[test]
+    fn allow_spurious_leading_newline_toggles_decode_outcome() {
+        let raw = &b"\r\nHTTP/1.1 200 OK\r\n\r\n"[..];
+
+        let mut strict = ClientCodec::with_config(ParserConfig::default());
+        assert!(strict.decode(&mut BytesMut::from(raw)).is_err());
+
+        let mut lenient = ClientCodec::with_config(ParserConfig {
+            allow_spurious_leading_newline: true,
+            ..Default::default()
+        });
+        let head = lenient
+            .decode(&mut BytesMut::from(raw))
+            .unwrap()
+            .expect("head decoded");
+        assert_eq!(head.status, StatusCode::OK);
+    }
+}